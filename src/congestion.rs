@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+/// Maximum segment size assumed by the congestion controller, in bytes.
+pub(crate) const MSS_BYTES: u32 = 1200;
+
+/// A NewReno-style congestion controller. Tracks the congestion window, slow-start threshold,
+/// and bytes in flight, and derives the number of bytes safe to send each frame from them. A
+/// socket implementation drives this by reporting delivery outcomes through [`Self::on_ack`] and
+/// [`Self::on_loss`] rather than hand-setting a budget.
+pub(crate) struct CongestionController {
+    cwnd_bytes: u32,
+    ssthresh_bytes: u32,
+    bytes_in_flight: u32,
+    last_rtt: Duration,
+    recovery_remaining: Duration,
+    /// Whether a loss collapse is currently in its one-per-RTT cooldown. Tracked independently of
+    /// `recovery_remaining` being zero, since `recovery_remaining` starts at `Duration::ZERO`
+    /// until the first RTT sample and would otherwise look indistinguishable from "no cooldown
+    /// active".
+    in_recovery: bool,
+}
+
+impl CongestionController {
+    /// Creates a new controller starting in slow start with a window of ten segments and an
+    /// unbounded slow-start threshold.
+    pub(crate) fn new() -> Self {
+        Self {
+            cwnd_bytes: 10 * MSS_BYTES,
+            ssthresh_bytes: u32::MAX,
+            bytes_in_flight: 0,
+            last_rtt: Duration::ZERO,
+            recovery_remaining: Duration::ZERO,
+            in_recovery: false,
+        }
+    }
+
+    /// Returns the number of bytes that may be sent this frame without exceeding the congestion
+    /// window.
+    pub(crate) fn frame_budget_bytes(&self) -> i32 {
+        (i64::from(self.cwnd_bytes) - i64::from(self.bytes_in_flight)).max(0) as i32
+    }
+
+    /// Marks `bytes` as sent and in flight. Called by the drain path as chunks go out.
+    pub(crate) fn on_send(&mut self, bytes: u32) {
+        self.bytes_in_flight = self.bytes_in_flight.saturating_add(bytes);
+    }
+
+    /// Reports that `acked_bytes` were acknowledged after a round trip of `rtt`, growing the
+    /// congestion window per the standard NewReno slow-start/congestion-avoidance rules.
+    pub(crate) fn on_ack(&mut self, acked_bytes: u32, rtt: Duration) {
+        self.bytes_in_flight = self.bytes_in_flight.saturating_sub(acked_bytes);
+        self.last_rtt = rtt;
+        if self.in_recovery {
+            self.recovery_remaining = self.recovery_remaining.saturating_sub(rtt);
+            if self.recovery_remaining.is_zero() {
+                self.in_recovery = false;
+            }
+        }
+
+        if self.cwnd_bytes < self.ssthresh_bytes {
+            // Slow start: grow by the number of bytes acknowledged.
+            self.cwnd_bytes = self.cwnd_bytes.saturating_add(acked_bytes);
+        } else {
+            // Congestion avoidance: grow by roughly one segment per window fully acknowledged.
+            let growth = u64::from(MSS_BYTES) * u64::from(acked_bytes) / u64::from(self.cwnd_bytes);
+            self.cwnd_bytes = self.cwnd_bytes.saturating_add(growth as u32);
+        }
+    }
+
+    /// Releases `bytes` of in-flight accounting for a send that's being given up on (e.g.
+    /// retransmitted after a timeout) rather than acknowledged. Unlike [`Self::on_ack`], this
+    /// never grows the window, since there's no evidence the bytes were actually delivered.
+    pub(crate) fn on_timeout(&mut self, bytes: u32) {
+        self.bytes_in_flight = self.bytes_in_flight.saturating_sub(bytes);
+    }
+
+    /// Reports a detected loss. Collapses the window at most once per RTT so a burst of losses
+    /// within the same round trip isn't counted multiple times.
+    pub(crate) fn on_loss(&mut self) {
+        if self.in_recovery {
+            return;
+        }
+        self.ssthresh_bytes = (self.cwnd_bytes / 2).max(2 * MSS_BYTES);
+        self.cwnd_bytes = self.ssthresh_bytes;
+        self.recovery_remaining = self.last_rtt;
+        self.in_recovery = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_initial_budget_is_ten_segments() {
+        let controller = CongestionController::new();
+        assert_eq!(controller.frame_budget_bytes(), (10 * MSS_BYTES) as i32);
+    }
+
+    #[test]
+    fn test_budget_shrinks_while_bytes_are_in_flight() {
+        let mut controller = CongestionController::new();
+        controller.on_send(MSS_BYTES);
+        assert_eq!(
+            controller.frame_budget_bytes(),
+            (9 * MSS_BYTES) as i32
+        );
+    }
+
+    #[test]
+    fn test_slow_start_grows_by_acked_bytes() {
+        let mut controller = CongestionController::new();
+        let cwnd_before = 10 * MSS_BYTES;
+        controller.on_send(MSS_BYTES);
+        controller.on_ack(MSS_BYTES, Duration::from_millis(50));
+        assert_eq!(controller.frame_budget_bytes(), (cwnd_before + MSS_BYTES) as i32);
+    }
+
+    #[test]
+    fn test_loss_halves_window_and_sets_ssthresh() {
+        let mut controller = CongestionController::new();
+        let cwnd_before = controller.cwnd_bytes;
+
+        controller.on_loss();
+
+        assert_eq!(controller.ssthresh_bytes, (cwnd_before / 2).max(2 * MSS_BYTES));
+        assert_eq!(controller.cwnd_bytes, controller.ssthresh_bytes);
+    }
+
+    #[test]
+    fn test_loss_only_collapses_once_per_rtt() {
+        let mut controller = CongestionController::new();
+        controller.on_ack(MSS_BYTES, Duration::from_millis(100));
+
+        controller.on_loss();
+        let cwnd_after_first_loss = controller.cwnd_bytes;
+
+        // A second loss reported within the same RTT should not collapse the window again.
+        controller.on_loss();
+        assert_eq!(controller.cwnd_bytes, cwnd_after_first_loss);
+
+        // Once a full RTT has elapsed, a new loss is allowed to collapse the window again.
+        controller.on_ack(0, Duration::from_millis(100));
+        controller.on_loss();
+        assert!(controller.cwnd_bytes <= cwnd_after_first_loss);
+    }
+
+    #[test]
+    fn test_loss_only_collapses_once_before_any_rtt_sample() {
+        let mut controller = CongestionController::new();
+
+        // No `on_ack` has ever landed, so `last_rtt` is still `Duration::ZERO`: the cooldown
+        // guard must not alias that with "no cooldown active".
+        controller.on_loss();
+        let cwnd_after_first_loss = controller.cwnd_bytes;
+
+        controller.on_loss();
+        assert_eq!(controller.cwnd_bytes, cwnd_after_first_loss);
+
+        // The first ack completes the outstanding round trip, lifting the cooldown.
+        controller.on_ack(0, Duration::from_millis(50));
+        controller.on_loss();
+        assert!(controller.cwnd_bytes <= cwnd_after_first_loss);
+    }
+}
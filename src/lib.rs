@@ -0,0 +1,12 @@
+//! A simple, transport-agnostic networking layer for Bevy.
+
+mod congestion;
+mod message;
+mod reliability;
+mod secure;
+mod sim;
+mod transport;
+
+pub use message::{Message, PRIO_BACKGROUND, PRIO_HIGH, PRIO_NORMAL};
+pub use secure::SecureFrameError;
+pub use transport::Transport;
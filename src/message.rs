@@ -0,0 +1,108 @@
+use std::net::SocketAddr;
+
+/// Highest-urgency priority class. Messages at this priority are drained ahead of every other
+/// class by [`Transport::drain_within_budget`](crate::Transport::drain_within_budget).
+pub const PRIO_HIGH: u8 = 0;
+
+/// Default priority used by [`Transport::send`](crate::Transport::send).
+pub const PRIO_NORMAL: u8 = 128;
+
+/// Lowest-urgency priority class. Messages at this priority are only drained once nothing at a
+/// higher priority remains pending.
+pub const PRIO_BACKGROUND: u8 = 255;
+
+/// A single unit of data to be sent over the network, along with the information needed to
+/// deliver it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub destination: SocketAddr,
+    pub payload: Vec<u8>,
+    /// Scheduling priority; lower values are sent first. See [`PRIO_HIGH`], [`PRIO_NORMAL`], and
+    /// [`PRIO_BACKGROUND`].
+    pub priority: u8,
+    /// Sequence number assigned to a reliable message, or `None` for the Socket's default
+    /// best-effort guarantees. See [`Transport::send_reliable`](crate::Transport::send_reliable).
+    pub sequence: Option<u64>,
+    /// Byte offset into `payload` up to which chunks have already been handed out. Lets a large
+    /// message resume where it left off across frames instead of restarting from scratch.
+    pub(crate) chunk_cursor: usize,
+}
+
+impl Message {
+    /// Creates a `Message` with the default guarantees provided by the `Socket` implementation
+    /// and [`PRIO_NORMAL`] priority.
+    #[must_use]
+    pub fn new(destination: SocketAddr, payload: &[u8]) -> Self {
+        Self::new_with_priority(destination, payload, PRIO_NORMAL)
+    }
+
+    /// Creates a `Message` carrying an explicit scheduling priority. Lower values are drained
+    /// first; see [`PRIO_HIGH`], [`PRIO_NORMAL`], and [`PRIO_BACKGROUND`].
+    #[must_use]
+    pub fn new_with_priority(destination: SocketAddr, payload: &[u8], priority: u8) -> Self {
+        Self {
+            destination,
+            payload: payload.to_vec(),
+            priority,
+            sequence: None,
+            chunk_cursor: 0,
+        }
+    }
+
+    /// Creates a reliable `Message` carrying the given sequence number, to be retransmitted
+    /// until the peer acknowledges it.
+    #[must_use]
+    pub(crate) fn new_reliable(
+        destination: SocketAddr,
+        payload: &[u8],
+        priority: u8,
+        sequence: u64,
+    ) -> Self {
+        Self {
+            destination,
+            payload: payload.to_vec(),
+            priority,
+            sequence: Some(sequence),
+            chunk_cursor: 0,
+        }
+    }
+
+    /// Creates a `Message` carrying a single already-cut chunk of a larger payload, preserving
+    /// the original message's sequence number so reliable sends survive chunking intact. Used by
+    /// [`Transport::drain_within_budget`](crate::Transport::drain_within_budget).
+    #[must_use]
+    pub(crate) fn new_chunk(
+        destination: SocketAddr,
+        chunk: &[u8],
+        priority: u8,
+        sequence: Option<u64>,
+    ) -> Self {
+        Self {
+            destination,
+            payload: chunk.to_vec(),
+            priority,
+            sequence,
+            chunk_cursor: 0,
+        }
+    }
+
+    /// Returns true if this message still has a chunk left to hand out.
+    pub(crate) fn has_remaining_chunk(&self) -> bool {
+        self.chunk_cursor < self.payload.len() || (self.payload.is_empty() && self.chunk_cursor == 0)
+    }
+
+    /// Length in bytes of the next chunk this message would yield, without consuming it.
+    pub(crate) fn peek_chunk_len(&self, chunk_size_bytes: usize) -> usize {
+        let end = (self.chunk_cursor + chunk_size_bytes).min(self.payload.len());
+        end - self.chunk_cursor
+    }
+
+    /// Consumes and returns the next chunk of the payload, advancing the cursor so the following
+    /// call picks up where this one left off.
+    pub(crate) fn take_chunk(&mut self, chunk_size_bytes: usize) -> Vec<u8> {
+        let end = (self.chunk_cursor + chunk_size_bytes).min(self.payload.len());
+        let chunk = self.payload[self.chunk_cursor..end].to_vec();
+        self.chunk_cursor = if self.payload.is_empty() { 1 } else { end };
+        chunk
+    }
+}
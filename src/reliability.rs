@@ -0,0 +1,115 @@
+use std::time::Instant;
+
+use super::message::Message;
+
+/// Minimum retransmission timeout, in nanoseconds, so a tiny or momentarily-zero latency
+/// estimate can't produce a pathologically aggressive retransmit loop.
+const MIN_RTO_NANOS: i64 = 100_000_000; // 100 ms
+
+/// Maximum retransmission timeout, in nanoseconds, that exponential backoff can reach.
+const MAX_RTO_NANOS: i64 = 60_000_000_000; // 60 s
+
+/// Computes the retransmission timeout for a message that has already been retransmitted
+/// `retries` times: RTT plus 4x the smoothed RTT variance (at least [`MIN_RTO_NANOS`]), doubled
+/// per retry for at most six retries, and never above [`MAX_RTO_NANOS`].
+pub(crate) fn compute_rto_nanos(latency_nanos: i64, rtt_variance_nanos: i64, retries: u32) -> i64 {
+    let base = (latency_nanos + 4 * rtt_variance_nanos).max(MIN_RTO_NANOS);
+    base.saturating_mul(1i64 << retries.min(6)).min(MAX_RTO_NANOS)
+}
+
+/// A reliable message that has been sent but not yet acknowledged.
+pub(crate) struct RetransmitEntry {
+    pub(crate) message: Message,
+    pub(crate) sent_at: Instant,
+    pub(crate) retries: u32,
+    /// Bytes of this message currently counted as in flight by the congestion controller, i.e.
+    /// handed to [`CongestionController::on_send`](super::congestion::CongestionController::on_send)
+    /// but not yet released by an ack or a retransmit timeout. Lets [`Transport::tick`]
+    /// (crate::Transport::tick) release exactly this many bytes, rather than leaking them
+    /// forever, when it gives up on the original send and re-enqueues the message.
+    pub(crate) bytes_in_flight: u32,
+}
+
+/// A compact, coalescing set of `u64` sequence numbers, stored as sorted inclusive ranges. Cheap
+/// to grow for long-lived connections with gaps, unlike a per-sequence-number bitmap.
+#[derive(Default)]
+pub(crate) struct SequenceRangeSet {
+    ranges: Vec<(u64, u64)>,
+}
+
+impl SequenceRangeSet {
+    pub(crate) fn new() -> Self {
+        Self { ranges: Vec::new() }
+    }
+
+    /// Inserts the inclusive range `[start, end]`, merging with any ranges it overlaps or
+    /// touches so the set stays coalesced.
+    pub(crate) fn insert(&mut self, start: u64, end: u64) {
+        let mut new_start = start;
+        let mut new_end = end;
+        let mut merged = Vec::with_capacity(self.ranges.len() + 1);
+        let mut inserted = false;
+
+        for &(s, e) in &self.ranges {
+            if e.saturating_add(1) < new_start {
+                merged.push((s, e));
+            } else if s > new_end.saturating_add(1) {
+                if !inserted {
+                    merged.push((new_start, new_end));
+                    inserted = true;
+                }
+                merged.push((s, e));
+            } else {
+                new_start = new_start.min(s);
+                new_end = new_end.max(e);
+            }
+        }
+        if !inserted {
+            merged.push((new_start, new_end));
+        }
+
+        self.ranges = merged;
+    }
+
+    /// Returns the coalesced ranges in ascending order.
+    pub(crate) fn ranges(&self) -> &[(u64, u64)] {
+        &self.ranges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_coalesces_adjacent_ranges() {
+        let mut set = SequenceRangeSet::new();
+        set.insert(1, 3);
+        set.insert(4, 6);
+        assert_eq!(set.ranges(), &[(1, 6)]);
+    }
+
+    #[test]
+    fn test_insert_keeps_gaps_separate() {
+        let mut set = SequenceRangeSet::new();
+        set.insert(1, 3);
+        set.insert(10, 12);
+        assert_eq!(set.ranges(), &[(1, 3), (10, 12)]);
+    }
+
+    #[test]
+    fn test_insert_merges_overlapping_and_out_of_order_ranges() {
+        let mut set = SequenceRangeSet::new();
+        set.insert(10, 12);
+        set.insert(1, 3);
+        set.insert(2, 11);
+        assert_eq!(set.ranges(), &[(1, 12)]);
+    }
+
+    #[test]
+    fn test_compute_rto_backs_off_exponentially() {
+        let first = compute_rto_nanos(50_000_000, 10_000_000, 0);
+        let second = compute_rto_nanos(50_000_000, 10_000_000, 1);
+        assert_eq!(second, first * 2);
+    }
+}
@@ -0,0 +1,185 @@
+use std::{collections::VecDeque, time::Instant};
+
+use super::message::Message;
+
+/// A small, seedable xorshift64 PRNG used to drive the network simulator deterministically so
+/// the same seed always reproduces the same drops, delays, and jitter.
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        // xorshift64 never advances from an all-zero state.
+        Self { state: seed | 1 }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+
+    /// Returns true with probability `p`, clamped to `[0, 1]`.
+    pub(crate) fn chance(&mut self, p: f32) -> bool {
+        let roll = (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32;
+        roll < p.clamp(0.0, 1.0)
+    }
+
+    /// Returns a pseudorandom `i64` uniformly distributed in `[-range, range]`.
+    pub(crate) fn jitter(&mut self, range: i64) -> i64 {
+        if range <= 0 {
+            return 0;
+        }
+        let span = 2 * range as u64 + 1;
+        (self.next_u64() % span) as i64 - range
+    }
+}
+
+struct DelayedMessage {
+    message: Message,
+    ready_at: Instant,
+}
+
+/// Holds messages that survived simulated packet loss until their simulated one-way latency has
+/// elapsed.
+pub(crate) struct DelayBuffer {
+    entries: VecDeque<DelayedMessage>,
+}
+
+impl DelayBuffer {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, message: Message, ready_at: Instant) {
+        self.entries.push_back(DelayedMessage { message, ready_at });
+    }
+
+    /// Removes and returns every message whose simulated delay has elapsed by `now`. With
+    /// `reorder` false, only the front of the buffer is considered, in original send order, so a
+    /// later message can never surface ahead of an earlier one still waiting. With `reorder`
+    /// true, any ready message anywhere in the buffer may surface, letting delayed messages
+    /// arrive out of order the way jitter over a real unordered transport would.
+    pub(crate) fn drain_ready(&mut self, now: Instant, reorder: bool) -> Vec<Message> {
+        let mut drained = Vec::new();
+
+        if reorder {
+            let mut i = 0;
+            while i != self.entries.len() {
+                if self.entries[i].ready_at <= now {
+                    drained.push(self.entries.remove(i).unwrap().message);
+                } else {
+                    i += 1;
+                }
+            }
+        } else {
+            while let Some(front) = self.entries.front() {
+                if front.ready_at > now {
+                    break;
+                }
+                drained.push(self.entries.pop_front().unwrap().message);
+            }
+        }
+
+        drained
+    }
+}
+
+/// Configuration and state for the built-in network-condition simulator. Once enabled, it makes
+/// `latency_nanos` and `packet_loss` actually apply to drained messages instead of remaining
+/// read-only estimates, so a game can validate interpolation and reliability logic against a
+/// reproducible lossy, delayed link without external tooling.
+pub(crate) struct NetworkSimulator {
+    pub(crate) rng: Rng,
+    pub(crate) jitter_nanos: i64,
+    pub(crate) reorder: bool,
+    pub(crate) delayed: DelayBuffer,
+}
+
+impl NetworkSimulator {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            jitter_nanos: 0,
+            reorder: false,
+            delayed: DelayBuffer::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn addr() -> std::net::SocketAddr {
+        "127.0.0.1:3000".parse().unwrap()
+    }
+
+    #[test]
+    fn test_delay_buffer_holds_messages_until_ready() {
+        let mut buffer = DelayBuffer::new();
+        let now = Instant::now();
+        buffer.push(Message::new(addr(), b"a"), now + Duration::from_millis(10));
+
+        assert!(buffer.drain_ready(now, false).is_empty());
+        assert_eq!(
+            buffer.drain_ready(now + Duration::from_millis(10), false).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_delay_buffer_preserves_order_without_reorder() {
+        let mut buffer = DelayBuffer::new();
+        let now = Instant::now();
+        // "b" becomes ready before "a", but without reorder it must wait behind "a".
+        buffer.push(Message::new(addr(), b"a"), now + Duration::from_millis(20));
+        buffer.push(Message::new(addr(), b"b"), now + Duration::from_millis(5));
+
+        assert!(buffer
+            .drain_ready(now + Duration::from_millis(10), false)
+            .is_empty());
+
+        let drained = buffer.drain_ready(now + Duration::from_millis(20), false);
+        let payloads: Vec<&[u8]> = drained.iter().map(|m| m.payload.as_slice()).collect();
+        assert_eq!(payloads, vec![b"a", b"b"]);
+    }
+
+    #[test]
+    fn test_delay_buffer_allows_out_of_order_with_reorder() {
+        let mut buffer = DelayBuffer::new();
+        let now = Instant::now();
+        buffer.push(Message::new(addr(), b"a"), now + Duration::from_millis(20));
+        buffer.push(Message::new(addr(), b"b"), now + Duration::from_millis(5));
+
+        let drained = buffer.drain_ready(now + Duration::from_millis(10), true);
+        let payloads: Vec<&[u8]> = drained.iter().map(|m| m.payload.as_slice()).collect();
+        assert_eq!(payloads, vec![b"b"]);
+    }
+
+    #[test]
+    fn test_rng_chance_is_deterministic_for_a_given_seed() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let rolls_a: Vec<bool> = (0..20).map(|_| a.chance(0.5)).collect();
+        let rolls_b: Vec<bool> = (0..20).map(|_| b.chance(0.5)).collect();
+        assert_eq!(rolls_a, rolls_b);
+    }
+
+    #[test]
+    fn test_rng_jitter_stays_within_range() {
+        let mut rng = Rng::new(7);
+        for _ in 0..100 {
+            let j = rng.jitter(10);
+            assert!((-10..=10).contains(&j));
+        }
+    }
+}
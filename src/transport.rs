@@ -1,14 +1,37 @@
-use std::{collections::VecDeque, net::SocketAddr};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
 
-use super::message::Message;
+use super::congestion::CongestionController;
+use super::message::{Message, PRIO_NORMAL};
+use super::reliability::{compute_rto_nanos, RetransmitEntry, SequenceRangeSet};
+use super::secure::{SecureChannelState, SecureFrameError};
+use super::sim::NetworkSimulator;
+
+/// Default size, in bytes, above which a message's payload is split into multiple chunks by
+/// [`Transport::drain_within_budget`]. Kept comfortably below the congestion controller's default
+/// initial window (10 * MSS_BYTES = 12,000 bytes) so the first chunk of a message always fits a
+/// fresh connection's budget, instead of stalling forever waiting for a window that only grows
+/// once something has actually been sent.
+pub const DEFAULT_CHUNK_SIZE_BYTES: usize = 4 * 1024;
 
 /// Resource serving as the owner of the queue of messages to be sent. This resource also serves
 /// as the interface for other systems to send messages.
 pub struct Transport {
     messages: VecDeque<Message>,
-    frame_budget_bytes: i32,
+    congestion: CongestionController,
     latency_nanos: i64,
+    rtt_variance_nanos: i64,
     packet_loss: f32,
+    chunk_size_bytes: usize,
+    next_sequence: u64,
+    retransmit_buffer: HashMap<u64, RetransmitEntry>,
+    received_ack_ranges: SequenceRangeSet,
+    secure_channels: HashMap<SocketAddr, SecureChannelState>,
+    used_send_keys: HashSet<[u8; 32]>,
+    network_sim: Option<NetworkSimulator>,
 }
 
 impl Transport {
@@ -17,21 +40,38 @@ impl Transport {
     pub fn new() -> Self {
         Self {
             messages: VecDeque::new(),
-            frame_budget_bytes: 0,
+            congestion: CongestionController::new(),
             latency_nanos: 0,
+            rtt_variance_nanos: 0,
             packet_loss: 0.0,
+            chunk_size_bytes: DEFAULT_CHUNK_SIZE_BYTES,
+            next_sequence: 0,
+            retransmit_buffer: HashMap::new(),
+            received_ack_ranges: SequenceRangeSet::new(),
+            secure_channels: HashMap::new(),
+            used_send_keys: HashSet::new(),
+            network_sim: None,
         }
     }
 
-    /// Returns estimated number of bytes you can reliably send this frame.
+    /// Returns the number of bytes you can send this frame without exceeding the congestion
+    /// window computed from reported acknowledgements and losses.
     #[must_use]
     pub fn frame_budget_bytes(&self) -> i32 {
-        self.frame_budget_bytes
+        self.congestion.frame_budget_bytes()
+    }
+
+    /// Reports that `acked_bytes` were acknowledged after a round trip of `rtt`, growing the
+    /// congestion window and freeing up that many bytes of in-flight budget. Call this from a
+    /// socket implementation whenever it observes a delivery confirmation.
+    pub fn on_ack(&mut self, acked_bytes: u32, rtt: Duration) {
+        self.congestion.on_ack(acked_bytes, rtt);
     }
 
-    /// Sets the frame budget in bytes. This should be called by a transport implementation.
-    pub fn set_frame_budget_bytes(&mut self, budget: i32) {
-        self.frame_budget_bytes = budget;
+    /// Reports a detected packet loss, shrinking the congestion window. Call this from a socket
+    /// implementation whenever it observes a dropped or timed-out send.
+    pub fn on_loss(&mut self) {
+        self.congestion.on_loss();
     }
 
     /// Returns the estimated millisecond round-trip latency for messages.
@@ -51,7 +91,12 @@ impl Transport {
     }
 
     /// Sets the latency value. This should be called by a transport implementation.
+    ///
+    /// Also updates the smoothed RTT variance used by [`Transport::tick`] to compute
+    /// retransmission timeouts.
     pub fn set_latency_nanos(&mut self, latency: i64) {
+        let delta = (latency - self.latency_nanos).abs();
+        self.rtt_variance_nanos += (delta - self.rtt_variance_nanos) / 4;
         self.latency_nanos = latency;
     }
 
@@ -66,6 +111,18 @@ impl Transport {
         self.packet_loss = loss;
     }
 
+    /// Returns the chunk size, in bytes, above which a message's payload is split across
+    /// multiple frames by [`Transport::drain_within_budget`].
+    #[must_use]
+    pub fn chunk_size_bytes(&self) -> usize {
+        self.chunk_size_bytes
+    }
+
+    /// Sets the chunk size used by [`Transport::drain_within_budget`].
+    pub fn set_chunk_size_bytes(&mut self, chunk_size_bytes: usize) {
+        self.chunk_size_bytes = chunk_size_bytes;
+    }
+
     /// Creates a `Message` with the default guarantees provided by the `Socket` implementation and
     /// pushes it onto the messages queue to be sent on the next frame.
     pub fn send(&mut self, destination: SocketAddr, payload: &[u8]) {
@@ -73,6 +130,148 @@ impl Transport {
         self.messages.push_back(message);
     }
 
+    /// Creates a `Message` with an explicit scheduling priority and pushes it onto the messages
+    /// queue to be sent on the next frame. Lower `priority` values are drained first by
+    /// [`Transport::drain_within_budget`]; see `PRIO_HIGH`, `PRIO_NORMAL`, and `PRIO_BACKGROUND`.
+    pub fn send_with_priority(&mut self, destination: SocketAddr, payload: &[u8], priority: u8) {
+        let message = Message::new_with_priority(destination, payload, priority);
+        self.messages.push_back(message);
+    }
+
+    /// Assigns the next sequence number to `payload`, keeps it in the retransmission buffer
+    /// until [`Transport::on_ack_ranges`] reports it delivered, and pushes it onto the queue to
+    /// be sent on the next frame. Returns the assigned sequence number.
+    pub fn send_reliable(&mut self, destination: SocketAddr, payload: &[u8]) -> u64 {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+
+        let message = Message::new_reliable(destination, payload, PRIO_NORMAL, sequence);
+        self.retransmit_buffer.insert(
+            sequence,
+            RetransmitEntry {
+                message: message.clone(),
+                sent_at: Instant::now(),
+                retries: 0,
+                bytes_in_flight: 0,
+            },
+        );
+        self.messages.push_back(message);
+
+        sequence
+    }
+
+    /// Marks the sequence numbers in `ranges` (inclusive) as delivered, dropping them from the
+    /// retransmission buffer.
+    pub fn on_ack_ranges(&mut self, ranges: &[(u64, u64)]) {
+        for &(start, end) in ranges {
+            self.retransmit_buffer
+                .retain(|&sequence, _| !(sequence >= start && sequence <= end));
+        }
+    }
+
+    /// Re-enqueues any reliable message whose retransmission timeout has elapsed since it was
+    /// last sent. The timeout is `latency_nanos` plus 4x the smoothed RTT variance, doubled for
+    /// each retransmit already attempted up to six, and kept between 100 ms and 60 s.
+    ///
+    /// A timeout is treated as a loss: it reports one to the congestion controller and releases
+    /// the bytes of the original send from in-flight accounting, since they'll never be acked and
+    /// the resent message will count its own bytes as it's drained again.
+    pub fn tick(&mut self, now: Instant) {
+        let mut expired = Vec::new();
+        for (&sequence, entry) in &self.retransmit_buffer {
+            let rto_nanos = compute_rto_nanos(self.latency_nanos, self.rtt_variance_nanos, entry.retries);
+            if now.duration_since(entry.sent_at).as_nanos() as i64 >= rto_nanos {
+                expired.push(sequence);
+            }
+        }
+
+        for sequence in expired {
+            let entry = self
+                .retransmit_buffer
+                .get_mut(&sequence)
+                .expect("sequence was just collected from this map");
+            self.congestion.on_timeout(entry.bytes_in_flight);
+            self.congestion.on_loss();
+            entry.bytes_in_flight = 0;
+
+            let mut message = entry.message.clone();
+            message.chunk_cursor = 0;
+            self.messages.push_back(message);
+            entry.sent_at = now;
+            entry.retries += 1;
+        }
+    }
+
+    /// Records that a reliable message carrying `sequence` was received, so it's included in the
+    /// ranges reported by [`Transport::ack_ranges`] for the peer to build its own range ack from.
+    pub fn record_received(&mut self, sequence: u64) {
+        self.received_ack_ranges.insert(sequence, sequence);
+    }
+
+    /// Returns the coalesced ranges of sequence numbers received so far that should be
+    /// acknowledged back to the peer.
+    #[must_use]
+    pub fn ack_ranges(&self) -> &[(u64, u64)] {
+        self.received_ack_ranges.ranges()
+    }
+
+    /// Establishes the secure channel used to encrypt and authenticate frames sent to, and decode
+    /// frames received from, `destination`. `send_key` and `receive_key` must be the 256-bit
+    /// directional keys produced by a prior handshake, and must match the peer's own receive and
+    /// send keys respectively.
+    ///
+    /// Returns [`SecureFrameError::KeyReuse`] if `send_key` has already been used to establish a
+    /// channel, to this destination or any other: reusing it would restart its nonce space from
+    /// the same counter value, reproducing a keystream already used to encrypt earlier frames.
+    pub fn set_secure_channel(
+        &mut self,
+        destination: SocketAddr,
+        send_key: [u8; 32],
+        receive_key: [u8; 32],
+    ) -> Result<(), SecureFrameError> {
+        if !self.used_send_keys.insert(send_key) {
+            return Err(SecureFrameError::KeyReuse);
+        }
+        self.secure_channels.insert(
+            destination,
+            SecureChannelState::new(&send_key, &receive_key),
+        );
+        Ok(())
+    }
+
+    /// Encrypts and authenticates `payload` for `destination` using its secure channel, and
+    /// pushes the resulting frame onto the queue to be sent on the next frame in place of a
+    /// cleartext `Message`.
+    pub fn send_encrypted(
+        &mut self,
+        destination: SocketAddr,
+        payload: &[u8],
+    ) -> Result<(), SecureFrameError> {
+        let channel = self
+            .secure_channels
+            .get_mut(&destination)
+            .ok_or(SecureFrameError::NoSecureChannel)?;
+        let frame = channel.encode_frame(payload);
+        self.messages.push_back(Message::new(destination, &frame));
+        Ok(())
+    }
+
+    /// Decodes a secure frame received from `source`, verifying the header MAC before trusting
+    /// its declared payload length and the payload MAC before returning the plaintext. Returns
+    /// an error if no secure channel has been established with `source`, or if a tampered or
+    /// truncated frame fails authentication.
+    pub fn decode_secure_frame(
+        &self,
+        source: SocketAddr,
+        frame: &[u8],
+    ) -> Result<Vec<u8>, SecureFrameError> {
+        let channel = self
+            .secure_channels
+            .get(&source)
+            .ok_or(SecureFrameError::NoSecureChannel)?;
+        channel.decode_frame(frame)
+    }
+
     /// Returns true if there are messages enqueued to be sent.
     #[must_use]
     pub fn has_messages(&self) -> bool {
@@ -88,8 +287,14 @@ impl Transport {
     /// Drains the messages queue and returns the drained messages. The filter allows you to drain
     /// only messages that adhere to your filter. This might be useful in a scenario like draining
     /// messages with a particular urgency requirement.
+    ///
+    /// If network simulation is enabled via [`Transport::enable_network_simulation`], drained
+    /// messages are instead fed through the simulated link: each is dropped with probability
+    /// `packet_loss`, and survivors are held until roughly `latency_nanos / 2` (plus jitter) has
+    /// elapsed since they were queued, as measured against `now`.
     pub fn drain_messages_to_send(
         &mut self,
+        now: Instant,
         mut filter: impl FnMut(&mut Message) -> bool,
     ) -> Vec<Message> {
         let mut drained = Vec::with_capacity(self.messages.len());
@@ -103,24 +308,168 @@ impl Transport {
                 i += 1;
             }
         }
-        drained
+
+        self.apply_network_simulation(now, drained)
+    }
+
+    /// Feeds `drained` through the simulated link if network simulation is enabled, returning the
+    /// messages actually ready to go out `now`: each is dropped with probability `packet_loss`,
+    /// and survivors are held until roughly `latency_nanos / 2` (plus jitter) has elapsed since
+    /// they were queued. Shared by every drain path so simulated loss/delay/jitter can be
+    /// exercised regardless of which one a caller uses.
+    fn apply_network_simulation(&mut self, now: Instant, drained: Vec<Message>) -> Vec<Message> {
+        let Some(sim) = &mut self.network_sim else {
+            return drained;
+        };
+
+        for message in drained {
+            if sim.rng.chance(self.packet_loss) {
+                continue;
+            }
+            let delay_nanos = (self.latency_nanos / 2 + sim.rng.jitter(sim.jitter_nanos)).max(0);
+            sim.delayed.push(message, now + Duration::from_nanos(delay_nanos as u64));
+        }
+
+        sim.delayed.drain_ready(now, sim.reorder)
+    }
+
+    /// Enables the built-in network simulator, seeded for reproducible tests. Once enabled,
+    /// [`Transport::drain_messages_to_send`] drops messages with probability `packet_loss` and
+    /// delays the rest by roughly `latency_nanos / 2` instead of draining them immediately.
+    pub fn enable_network_simulation(&mut self, seed: u64) {
+        self.network_sim = Some(NetworkSimulator::new(seed));
+    }
+
+    /// Disables the network simulator; [`Transport::drain_messages_to_send`] goes back to
+    /// draining matching messages immediately.
+    pub fn disable_network_simulation(&mut self) {
+        self.network_sim = None;
+    }
+
+    /// Returns true if the network simulator is currently enabled.
+    #[must_use]
+    pub fn is_network_simulation_enabled(&self) -> bool {
+        self.network_sim.is_some()
+    }
+
+    /// Sets the uniform +/- jitter, in nanoseconds, applied to each simulated message's delay.
+    /// Has no effect unless network simulation is enabled.
+    pub fn set_simulated_jitter_nanos(&mut self, jitter_nanos: i64) {
+        if let Some(sim) = &mut self.network_sim {
+            sim.jitter_nanos = jitter_nanos;
+        }
+    }
+
+    /// Sets whether delayed messages may surface out of their original send order once their
+    /// simulated delay elapses. Has no effect unless network simulation is enabled.
+    pub fn set_simulated_reorder(&mut self, reorder: bool) {
+        if let Some(sim) = &mut self.network_sim {
+            sim.reorder = reorder;
+        }
+    }
+
+    /// Drains messages to send within `frame_budget_bytes`, splitting any message larger than
+    /// `chunk_size_bytes` into ordered chunks and resuming partially-sent messages across calls.
+    ///
+    /// Messages are scheduled by priority: only the single highest-priority class with pending
+    /// data is drained, one chunk per message in round-robin order, until either the budget is
+    /// exhausted or every message in that class is fully drained, at which point the next-lower
+    /// priority class is considered. Messages that still have remaining chunks stay in the queue
+    /// for the next frame.
+    ///
+    /// If network simulation is enabled via [`Transport::enable_network_simulation`], the chunks
+    /// drained this call are fed through the same simulated link as
+    /// [`Transport::drain_messages_to_send`]: each is dropped with probability `packet_loss`, and
+    /// survivors are held until roughly `latency_nanos / 2` (plus jitter) has elapsed since they
+    /// were queued, as measured against `now`. This lets simulated loss/delay/jitter be exercised
+    /// together with priority scheduling, the congestion window, and reliable retransmission.
+    pub fn drain_within_budget(&mut self, now: Instant) -> Vec<Message> {
+        let mut drained = Vec::new();
+
+        'priority_classes: loop {
+            let current_priority = self
+                .messages
+                .iter()
+                .filter(|m| m.has_remaining_chunk())
+                .map(|m| m.priority)
+                .min();
+
+            let Some(current_priority) = current_priority else {
+                break;
+            };
+
+            loop {
+                let mut sent_any = false;
+                let mut i = 0;
+                while i != self.messages.len() {
+                    if self.messages[i].priority != current_priority
+                        || !self.messages[i].has_remaining_chunk()
+                    {
+                        i += 1;
+                        continue;
+                    }
+
+                    let chunk_len = self.messages[i].peek_chunk_len(self.chunk_size_bytes);
+                    if chunk_len as i32 > self.congestion.frame_budget_bytes() {
+                        // Doesn't fit yet, but a smaller message behind it in the same priority
+                        // class still might; keep scanning instead of aborting the whole drain.
+                        i += 1;
+                        continue;
+                    }
+
+                    let destination = self.messages[i].destination;
+                    let sequence = self.messages[i].sequence;
+                    let chunk = self.messages[i].take_chunk(self.chunk_size_bytes);
+                    self.congestion.on_send(chunk_len as u32);
+                    sent_any = true;
+
+                    if let Some(sequence) = sequence {
+                        if let Some(entry) = self.retransmit_buffer.get_mut(&sequence) {
+                            entry.bytes_in_flight += chunk_len as u32;
+                        }
+                    }
+
+                    drained.push(Message::new_chunk(destination, &chunk, current_priority, sequence));
+
+                    if self.messages[i].has_remaining_chunk() {
+                        i += 1;
+                    } else {
+                        self.messages.remove(i);
+                    }
+                }
+
+                if !sent_any {
+                    let class_still_pending = self
+                        .messages
+                        .iter()
+                        .any(|m| m.priority == current_priority && m.has_remaining_chunk());
+
+                    if class_still_pending {
+                        // Something in the current (highest pending) priority class doesn't fit
+                        // the remaining budget; a lower class must not jump ahead of it, so stop
+                        // the whole drain.
+                        break 'priority_classes;
+                    }
+                    // The class is fully drained; move on to the next-lower priority class.
+                    break;
+                }
+            }
+        }
+
+        self.apply_network_simulation(now, drained)
     }
 }
 
 impl Default for Transport {
     fn default() -> Self {
-        Self {
-            messages: VecDeque::new(),
-            frame_budget_bytes: 0,
-            latency_nanos: 0,
-            packet_loss: 0.0,
-        }
+        Self::new()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::message::{PRIO_BACKGROUND, PRIO_HIGH, PRIO_NORMAL};
 
     #[test]
     fn test_send() {
@@ -137,14 +486,15 @@ mod tests {
     #[test]
     fn test_has_messages() {
         let mut transport = create_test_transport();
-        assert_eq!(transport.has_messages(), false);
+        assert!(!transport.has_messages());
         transport.send("127.0.0.1:3000".parse().unwrap(), test_payload());
-        assert_eq!(transport.has_messages(), true);
+        assert!(transport.has_messages());
     }
 
     #[test]
     fn test_drain_only_heartbeat_messages() {
         let mut transport = create_test_transport();
+        let now = Instant::now();
 
         let addr = "127.0.0.1:3000".parse().unwrap();
         transport.send(addr, test_payload());
@@ -155,21 +505,385 @@ mod tests {
 
         assert_eq!(
             transport
-                .drain_messages_to_send(|m| m.payload == heartbeat_payload())
+                .drain_messages_to_send(now, |m| m.payload == heartbeat_payload())
                 .len(),
             2
         );
         // validate removal
         assert_eq!(
             transport
-                .drain_messages_to_send(|m| m.payload == heartbeat_payload())
+                .drain_messages_to_send(now, |m| m.payload == heartbeat_payload())
                 .len(),
             0
         );
-        assert_eq!(transport.drain_messages_to_send(|_| false).len(), 0);
-        assert_eq!(transport.drain_messages_to_send(|_| true).len(), 3);
+        assert_eq!(transport.drain_messages_to_send(now, |_| false).len(), 0);
+        assert_eq!(transport.drain_messages_to_send(now, |_| true).len(), 3);
         // validate removal
-        assert_eq!(transport.drain_messages_to_send(|_| true).len(), 0);
+        assert_eq!(transport.drain_messages_to_send(now, |_| true).len(), 0);
+    }
+
+    #[test]
+    fn test_drain_messages_to_send_ignores_simulation_when_disabled() {
+        let mut transport = create_test_transport();
+        transport.set_latency_nanos(1_000_000_000);
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        transport.send(addr, test_payload());
+
+        assert_eq!(
+            transport
+                .drain_messages_to_send(Instant::now(), |_| true)
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_network_simulation_delays_messages_by_half_the_latency() {
+        let mut transport = create_test_transport();
+        transport.set_latency_nanos(100_000_000); // 100 ms round trip
+        transport.enable_network_simulation(1);
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let now = Instant::now();
+
+        transport.send(addr, test_payload());
+        assert!(transport.drain_messages_to_send(now, |_| true).is_empty());
+
+        let after_half_latency = now + Duration::from_millis(50);
+        assert_eq!(
+            transport
+                .drain_messages_to_send(after_half_latency, |_| true)
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_network_simulation_drops_messages_with_seeded_rng() {
+        let mut transport = create_test_transport();
+        transport.enable_network_simulation(1);
+        // A packet_loss of 1.0 should drop every message, deterministically for any seed.
+        transport.set_packet_loss(1.0);
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let now = Instant::now();
+
+        for _ in 0..10 {
+            transport.send(addr, test_payload());
+        }
+
+        assert!(transport
+            .drain_messages_to_send(now + Duration::from_secs(1), |_| true)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_disable_network_simulation_resumes_immediate_draining() {
+        let mut transport = create_test_transport();
+        transport.set_latency_nanos(1_000_000_000);
+        transport.enable_network_simulation(1);
+        assert!(transport.is_network_simulation_enabled());
+
+        transport.disable_network_simulation();
+        assert!(!transport.is_network_simulation_enabled());
+
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        transport.send(addr, test_payload());
+        assert_eq!(
+            transport
+                .drain_messages_to_send(Instant::now(), |_| true)
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_send_with_priority() {
+        let mut transport = create_test_transport();
+
+        transport.send_with_priority(
+            "127.0.0.1:3000".parse().unwrap(),
+            test_payload(),
+            PRIO_HIGH,
+        );
+
+        assert_eq!(transport.messages[0].priority, PRIO_HIGH);
+    }
+
+    #[test]
+    fn test_drain_within_budget_respects_priority() {
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let mut transport = create_test_transport();
+
+        transport.send_with_priority(addr, b"background", PRIO_BACKGROUND);
+        transport.send_with_priority(addr, b"high", PRIO_HIGH);
+        transport.send_with_priority(addr, b"normal", PRIO_NORMAL);
+
+        let drained = transport.drain_within_budget(Instant::now());
+
+        assert_eq!(drained[0].payload, b"high");
+        assert_eq!(drained[1].payload, b"normal");
+        assert_eq!(drained[2].payload, b"background");
+    }
+
+    #[test]
+    fn test_drain_within_budget_round_robins_same_priority() {
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let mut transport = create_test_transport();
+        transport.set_chunk_size_bytes(1);
+
+        transport.send(addr, b"ab");
+        transport.send(addr, b"cd");
+
+        let drained = transport.drain_within_budget(Instant::now());
+
+        let chunks: Vec<&[u8]> = drained.iter().map(|m| m.payload.as_slice()).collect();
+        assert_eq!(chunks, vec![b"a", b"c", b"b", b"d"]);
+    }
+
+    #[test]
+    fn test_drain_within_budget_skips_oversized_message_for_smaller_one_behind_it() {
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let mut transport = create_test_transport();
+        transport.set_chunk_size_bytes(9_000);
+
+        // Default budget is 10 * MSS_BYTES = 12000 bytes.
+        transport.send(addr, &vec![1u8; 9_000]); // fits; leaves 3000 bytes of budget
+        transport.send(addr, &vec![2u8; 20_000]); // next chunk is 9000 bytes; doesn't fit
+        transport.send(addr, &vec![3u8; 1_000]); // fits in the 3000 bytes left over
+
+        let drained = transport.drain_within_budget(Instant::now());
+        let total: usize = drained.iter().map(|m| m.payload.len()).sum();
+
+        assert_eq!(total, 10_000);
+        assert!(transport.has_messages());
+    }
+
+    #[test]
+    fn test_drain_within_budget_sends_default_sized_chunk_with_default_budget() {
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let mut transport = create_test_transport();
+
+        // Pure defaults: a message exactly at the default chunk size must still fit the
+        // default initial congestion window, or it can never be drained at all.
+        transport.send(addr, &vec![0u8; DEFAULT_CHUNK_SIZE_BYTES]);
+
+        let mut total_drained = 0;
+        for _ in 0..5 {
+            let drained = transport.drain_within_budget(Instant::now());
+            total_drained += drained.iter().map(|m| m.payload.len()).sum::<usize>();
+        }
+
+        assert_eq!(total_drained, DEFAULT_CHUNK_SIZE_BYTES);
+        assert!(!transport.has_messages());
+    }
+
+    #[test]
+    fn test_drain_within_budget_preserves_sequence_through_chunking() {
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let mut transport = create_test_transport();
+        transport.set_chunk_size_bytes(4_000);
+
+        let sequence = transport.send_reliable(addr, &vec![9u8; 8_000]);
+
+        let first = transport.drain_within_budget(Instant::now());
+        assert!(first.iter().all(|m| m.sequence == Some(sequence)));
+
+        transport.on_ack(4_000, Duration::from_millis(50));
+        let second = transport.drain_within_budget(Instant::now());
+        assert!(second.iter().all(|m| m.sequence == Some(sequence)));
+    }
+
+    #[test]
+    fn test_drain_within_budget_splits_large_message_and_resumes() {
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let mut transport = create_test_transport();
+        // Smaller than the default congestion window, so the first drain can't take it all in
+        // one go and must resume from its chunk cursor on the next call.
+        transport.set_chunk_size_bytes(4_000);
+
+        transport.send(addr, &vec![7u8; 20_000]);
+
+        let first = transport.drain_within_budget(Instant::now());
+        let first_len: usize = first.iter().map(|m| m.payload.len()).sum();
+        assert!(first_len > 0 && first_len < 20_000);
+        assert!(transport.has_messages());
+
+        // Nothing more can go out until the in-flight bytes are acknowledged.
+        assert_eq!(transport.drain_within_budget(Instant::now()).len(), 0);
+
+        transport.on_ack(first_len as u32, Duration::from_millis(50));
+
+        let second = transport.drain_within_budget(Instant::now());
+        let second_len: usize = second.iter().map(|m| m.payload.len()).sum();
+        assert_eq!(first_len + second_len, 20_000);
+        assert!(!transport.has_messages());
+    }
+
+    #[test]
+    fn test_drain_within_budget_blocks_until_acked() {
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let mut transport = create_test_transport();
+        transport.set_chunk_size_bytes(usize::MAX);
+        // Shrink the frame budget to zero by putting the whole congestion window in flight.
+        let cwnd = transport.frame_budget_bytes() as u32;
+        transport.congestion.on_send(cwnd);
+        assert_eq!(transport.frame_budget_bytes(), 0);
+
+        transport.send(addr, test_payload());
+
+        assert_eq!(transport.drain_within_budget(Instant::now()).len(), 0);
+        assert!(transport.has_messages());
+    }
+
+    #[test]
+    fn test_drain_within_budget_applies_network_simulation() {
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let mut transport = create_test_transport();
+        transport.enable_network_simulation(1);
+
+        // A packet_loss of 1.0 should drop every chunk, deterministically for any seed, even
+        // though it went through the priority/congestion-aware drain path rather than the plain
+        // filter-based one.
+        transport.set_packet_loss(1.0);
+        transport.send_with_priority(addr, test_payload(), PRIO_HIGH);
+
+        assert!(transport
+            .drain_within_budget(Instant::now() + Duration::from_secs(1))
+            .is_empty());
+        assert!(!transport.has_messages());
+    }
+
+    #[test]
+    fn test_send_reliable_assigns_increasing_sequence_numbers() {
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let mut transport = create_test_transport();
+
+        let first = transport.send_reliable(addr, test_payload());
+        let second = transport.send_reliable(addr, test_payload());
+
+        assert_eq!(first, 0);
+        assert_eq!(second, 1);
+        assert_eq!(transport.messages[0].sequence, Some(first));
+        assert_eq!(transport.messages[1].sequence, Some(second));
+    }
+
+    #[test]
+    fn test_on_ack_ranges_drops_acked_messages_from_retransmit_buffer() {
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let mut transport = create_test_transport();
+
+        let sequence = transport.send_reliable(addr, test_payload());
+        assert!(transport.retransmit_buffer.contains_key(&sequence));
+
+        transport.on_ack_ranges(&[(sequence, sequence)]);
+
+        assert!(!transport.retransmit_buffer.contains_key(&sequence));
+    }
+
+    #[test]
+    fn test_tick_retransmits_after_rto_elapses() {
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let mut transport = create_test_transport();
+        transport.set_latency_nanos(10_000_000);
+
+        let sequence = transport.send_reliable(addr, test_payload());
+        transport.drain_within_budget(Instant::now());
+        assert!(!transport.has_messages());
+
+        let sent_at = transport.retransmit_buffer[&sequence].sent_at;
+        let not_yet_due = sent_at + Duration::from_millis(1);
+        transport.tick(not_yet_due);
+        assert!(!transport.has_messages());
+
+        let well_past_due = sent_at + Duration::from_secs(10);
+        transport.tick(well_past_due);
+        assert!(transport.has_messages());
+        assert_eq!(transport.retransmit_buffer[&sequence].retries, 1);
+    }
+
+    #[test]
+    fn test_tick_releases_in_flight_bytes_instead_of_leaking_them_on_retransmit() {
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let mut transport = create_test_transport();
+        transport.set_latency_nanos(10_000_000);
+        transport.set_chunk_size_bytes(usize::MAX);
+
+        transport.send_reliable(addr, &vec![0u8; 1_000]);
+        transport.drain_within_budget(Instant::now());
+
+        let sent_at = transport.retransmit_buffer[&0].sent_at;
+        transport.tick(sent_at + Duration::from_secs(10));
+        transport.drain_within_budget(Instant::now());
+        transport.on_ack(1_000, Duration::from_millis(50));
+
+        // The budget should reflect only the bytes genuinely still in flight: none. If the
+        // original (presumed-lost) send's bytes were never released from accounting, this would
+        // come up 1000 bytes short of the real window forever.
+        let budget = transport.frame_budget_bytes();
+        let mut nothing_in_flight = CongestionController::new();
+        nothing_in_flight.on_loss();
+        nothing_in_flight.on_ack(1_000, Duration::from_millis(50));
+        assert_eq!(budget, nothing_in_flight.frame_budget_bytes());
+        transport.send(addr, &vec![1u8; budget as usize]);
+        assert_eq!(transport.drain_within_budget(Instant::now())[0].payload.len(), budget as usize);
+    }
+
+    #[test]
+    fn test_record_received_exposes_coalesced_ack_ranges() {
+        let mut transport = create_test_transport();
+
+        transport.record_received(1);
+        transport.record_received(2);
+        transport.record_received(3);
+        transport.record_received(10);
+
+        assert_eq!(transport.ack_ranges(), &[(1, 3), (10, 10)]);
+    }
+
+    #[test]
+    fn test_send_encrypted_requires_a_secure_channel() {
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let mut transport = create_test_transport();
+
+        assert_eq!(
+            transport.send_encrypted(addr, test_payload()),
+            Err(SecureFrameError::NoSecureChannel)
+        );
+    }
+
+    #[test]
+    fn test_send_encrypted_round_trips_through_decode_secure_frame() {
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let mut sender = create_test_transport();
+        let mut receiver = create_test_transport();
+
+        sender.set_secure_channel(addr, key(1), key(2)).unwrap();
+        // The receiver's "receive" key is the sender's "send" key, and vice versa.
+        receiver.set_secure_channel(addr, key(2), key(1)).unwrap();
+
+        sender.send_encrypted(addr, test_payload()).unwrap();
+        let frame = &sender.messages[0].payload;
+
+        assert_eq!(receiver.decode_secure_frame(addr, frame).unwrap(), test_payload());
+    }
+
+    #[test]
+    fn test_set_secure_channel_rejects_reused_send_key() {
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let other_addr = "127.0.0.1:3001".parse().unwrap();
+        let mut transport = create_test_transport();
+
+        transport.set_secure_channel(addr, key(1), key(2)).unwrap();
+
+        // Reusing the same send key, even for a different destination, would restart its nonce
+        // space from a counter value already used to encrypt earlier frames.
+        assert_eq!(
+            transport.set_secure_channel(other_addr, key(1), key(3)),
+            Err(SecureFrameError::KeyReuse)
+        );
+    }
+
+    fn key(byte: u8) -> [u8; 32] {
+        [byte; 32]
     }
 
     fn heartbeat_payload() -> &'static [u8] {